@@ -1,52 +1,484 @@
 use std::{
-    convert::Infallible,
     fmt::{self, Display, Formatter},
-    net::SocketAddr,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
 };
 
 use datasize::DataSize;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use casper_types::{
+    bytesrepr::{self, FromBytes, ToBytes, U8_SERIALIZED_LENGTH},
+    crypto, Digest, PublicKey, SecretKey, Signature,
+};
 
 use crate::{
     effect::GossipTarget,
-    types::{GossipItem, Tag},
+    types::{GossipItem, Tag, TimeDiff, Timestamp},
 };
 
-/// Used to gossip our public listening address to peers.
-#[derive(
-    Copy, Clone, DataSize, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, Debug,
-)]
-pub struct GossipedAddress(SocketAddr);
+/// The default window either side of "now" within which a gossiped address' timestamp is
+/// considered fresh, used when a caller doesn't have a configured value to hand.  Addresses
+/// gossiped outside this window are rejected to prevent replay of stale signatures.
+const DEFAULT_ADDRESS_FRESHNESS_WINDOW: TimeDiff = TimeDiff::from_seconds(5 * 60);
+
+/// The maximum number of endpoints a single `GossipedAddress` may advertise.  Bounded so that
+/// deserializing a hostile peer's gossip can't be used to force unbounded allocation.
+const MAX_GOSSIPED_ENDPOINTS: usize = 4;
+
+/// The error type returned when a `GossipedAddress` fails validation.
+#[derive(Clone, Eq, PartialEq, Debug, Error)]
+pub enum GossipedAddressValidationError {
+    /// The signature over the endpoints and timestamp doesn't match the claimed public key.
+    #[error("invalid signature on gossiped address")]
+    InvalidSignature,
+    /// The timestamp is too far from the current time to be trusted.
+    #[error("gossiped address timestamp is stale")]
+    StaleTimestamp,
+}
+
+/// The error type returned when constructing a `GossipedAddress` from an invalid set of
+/// endpoints.
+#[derive(Clone, Eq, PartialEq, Debug, Error)]
+pub enum GossipedAddressConstructionError {
+    /// No endpoints were provided to gossip.
+    #[error("cannot construct a gossiped address with no endpoints")]
+    NoEndpoints,
+    /// More than `MAX_GOSSIPED_ENDPOINTS` endpoints were provided.
+    #[error(
+        "cannot construct a gossiped address with {actual} endpoints, the maximum is {}",
+        MAX_GOSSIPED_ENDPOINTS
+    )]
+    TooManyEndpoints {
+        /// The number of endpoints that were provided.
+        actual: usize,
+    },
+}
+
+/// The transport a gossiped endpoint is reachable over.
+#[derive(Copy, Clone, DataSize, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+pub enum Transport {
+    /// A plain TCP listener.
+    Tcp,
+    /// A QUIC listener.
+    Quic,
+}
+
+/// A single address at which a node may be dialed, together with the transport it speaks there.
+#[derive(Copy, Clone, DataSize, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+pub struct Endpoint {
+    /// The socket address to dial.
+    pub address: SocketAddr,
+    /// The transport listening at `address`.
+    pub transport: Transport,
+}
+
+impl Endpoint {
+    pub(super) fn new(address: SocketAddr, transport: Transport) -> Self {
+        Endpoint { address, transport }
+    }
+}
+
+/// Used to gossip our public listening addresses to peers.
+///
+/// A node may be reachable over several transports or address families at once (e.g. separate
+/// IPv4/IPv6 or TCP/QUIC listeners), so the gossiped item carries an ordered, bounded list of
+/// `Endpoint`s rather than a single address; peers should try them in the order given when
+/// dialing.  The endpoints are accompanied by the originating node's public key and a signature
+/// over the endpoints and a freshness timestamp, so that peers can cryptographically attribute
+/// them to the node advertising them rather than accepting arbitrary unsigned claims.
+#[derive(Clone, DataSize, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+pub struct GossipedAddress {
+    endpoints: Vec<Endpoint>,
+    public_key: PublicKey,
+    timestamp: Timestamp,
+    signature: Signature,
+}
 
 impl GossipedAddress {
-    pub(super) fn new(address: SocketAddr) -> Self {
-        GossipedAddress(address)
+    pub(super) fn new(
+        endpoints: Vec<Endpoint>,
+        timestamp: Timestamp,
+        secret_key: &SecretKey,
+    ) -> Result<Self, GossipedAddressConstructionError> {
+        if endpoints.is_empty() {
+            return Err(GossipedAddressConstructionError::NoEndpoints);
+        }
+        if endpoints.len() > MAX_GOSSIPED_ENDPOINTS {
+            return Err(GossipedAddressConstructionError::TooManyEndpoints {
+                actual: endpoints.len(),
+            });
+        }
+
+        let public_key = PublicKey::from(secret_key);
+        let signature = crypto::sign(
+            Self::signing_payload(&endpoints, timestamp),
+            secret_key,
+            &public_key,
+        );
+        Ok(GossipedAddress {
+            endpoints,
+            public_key,
+            timestamp,
+            signature,
+        })
+    }
+
+    /// Returns the addresses to dial, in preference order.
+    pub(super) fn addresses(&self) -> impl Iterator<Item = &Endpoint> {
+        self.endpoints.iter()
+    }
+
+    /// Returns the bytes which are signed over: the endpoints followed by the timestamp.
+    fn signing_payload(endpoints: &[Endpoint], timestamp: Timestamp) -> Vec<u8> {
+        let mut payload = endpoints_to_bytes(endpoints);
+        payload.extend(
+            timestamp
+                .to_bytes()
+                .unwrap_or_else(|_| panic!("should serialize timestamp")),
+        );
+        payload
+    }
+
+    /// Validates this address against the given freshness window, which callers should derive
+    /// from their chainspec/network config where one is available, falling back to
+    /// [`DEFAULT_ADDRESS_FRESHNESS_WINDOW`] otherwise.
+    fn validate_with_freshness_window(
+        &self,
+        freshness_window: TimeDiff,
+    ) -> Result<(), GossipedAddressValidationError> {
+        let now = Timestamp::now();
+        let age = if now >= self.timestamp {
+            now - self.timestamp
+        } else {
+            self.timestamp - now
+        };
+        if age > freshness_window {
+            return Err(GossipedAddressValidationError::StaleTimestamp);
+        }
+
+        crypto::verify(
+            Self::signing_payload(&self.endpoints, self.timestamp),
+            &self.signature,
+            &self.public_key,
+        )
+        .map_err(|_| GossipedAddressValidationError::InvalidSignature)
     }
 }
 
 impl Display for GossipedAddress {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
-        write!(formatter, "gossiped-address {}", self.0)
+        write!(formatter, "gossiped-address [")?;
+        for (index, endpoint) in self.endpoints.iter().enumerate() {
+            if index > 0 {
+                write!(formatter, ", ")?;
+            }
+            write!(formatter, "{}", endpoint.address)?;
+        }
+        write!(formatter, "] from {}", self.public_key)
     }
 }
 
 impl GossipItem for GossipedAddress {
-    type Id = GossipedAddress;
-    type ValidationError = Infallible;
-    const ID_IS_COMPLETE_ITEM: bool = true;
+    type Id = Digest;
+    type ValidationError = GossipedAddressValidationError;
+    const ID_IS_COMPLETE_ITEM: bool = false;
     const TAG: Tag = Tag::GossipedAddress;
 
     fn id(&self) -> Self::Id {
-        *self
+        let mut payload = endpoints_to_bytes(&self.endpoints);
+        payload.extend(
+            self.public_key
+                .to_bytes()
+                .unwrap_or_else(|_| panic!("should serialize public key")),
+        );
+        Digest::hash(payload)
     }
 
     fn target(&self) -> GossipTarget {
         GossipTarget::All
     }
+
+    fn validate(&self) -> Result<(), Self::ValidationError> {
+        self.validate_with_freshness_window(DEFAULT_ADDRESS_FRESHNESS_WINDOW)
+    }
+}
+
+impl ToBytes for GossipedAddress {
+    fn to_bytes(&self) -> Result<Vec<u8>, bytesrepr::Error> {
+        let mut buffer = endpoints_to_bytes(&self.endpoints);
+        buffer.extend(self.public_key.to_bytes()?);
+        buffer.extend(self.timestamp.to_bytes()?);
+        buffer.extend(self.signature.to_bytes()?);
+        Ok(buffer)
+    }
+
+    fn serialized_length(&self) -> usize {
+        endpoints_serialized_length(&self.endpoints)
+            + self.public_key.serialized_length()
+            + self.timestamp.serialized_length()
+            + self.signature.serialized_length()
+    }
+}
+
+impl FromBytes for GossipedAddress {
+    fn from_bytes(bytes: &[u8]) -> Result<(Self, &[u8]), bytesrepr::Error> {
+        let (endpoints, remainder) = endpoints_from_bytes(bytes)?;
+        let (public_key, remainder) = PublicKey::from_bytes(remainder)?;
+        let (timestamp, remainder) = Timestamp::from_bytes(remainder)?;
+        let (signature, remainder) = Signature::from_bytes(remainder)?;
+        let gossiped_address = GossipedAddress {
+            endpoints,
+            public_key,
+            timestamp,
+            signature,
+        };
+        Ok((gossiped_address, remainder))
+    }
+}
+
+const SOCKET_ADDR_V4_TAG: u8 = 0;
+const SOCKET_ADDR_V6_TAG: u8 = 1;
+
+const TRANSPORT_TCP_TAG: u8 = 0;
+const TRANSPORT_QUIC_TAG: u8 = 1;
+
+fn transport_tag(transport: Transport) -> u8 {
+    match transport {
+        Transport::Tcp => TRANSPORT_TCP_TAG,
+        Transport::Quic => TRANSPORT_QUIC_TAG,
+    }
+}
+
+fn transport_from_tag(tag: u8) -> Result<Transport, bytesrepr::Error> {
+    match tag {
+        TRANSPORT_TCP_TAG => Ok(Transport::Tcp),
+        TRANSPORT_QUIC_TAG => Ok(Transport::Quic),
+        _ => Err(bytesrepr::Error::Formatting),
+    }
+}
+
+fn socket_addr_to_bytes(address: &SocketAddr) -> Vec<u8> {
+    match address {
+        SocketAddr::V4(v4) => {
+            let mut buffer = vec![SOCKET_ADDR_V4_TAG];
+            buffer.extend(v4.ip().octets());
+            buffer.extend(v4.port().to_be_bytes());
+            buffer
+        }
+        SocketAddr::V6(v6) => {
+            let mut buffer = vec![SOCKET_ADDR_V6_TAG];
+            buffer.extend(v6.ip().octets());
+            buffer.extend(v6.port().to_be_bytes());
+            buffer
+        }
+    }
+}
+
+fn socket_addr_serialized_length(address: &SocketAddr) -> usize {
+    match address {
+        SocketAddr::V4(_) => 1 + 4 + 2,
+        SocketAddr::V6(_) => 1 + 16 + 2,
+    }
+}
+
+fn socket_addr_from_bytes(bytes: &[u8]) -> Result<(SocketAddr, &[u8]), bytesrepr::Error> {
+    let (tag, remainder) = u8::from_bytes(bytes)?;
+    match tag {
+        SOCKET_ADDR_V4_TAG => {
+            if remainder.len() < 6 {
+                return Err(bytesrepr::Error::EarlyEndOfStream);
+            }
+            let (octets, remainder) = remainder.split_at(4);
+            let (port_bytes, remainder) = remainder.split_at(2);
+            let ip = Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]);
+            let port = u16::from_be_bytes([port_bytes[0], port_bytes[1]]);
+            Ok((SocketAddr::new(IpAddr::V4(ip), port), remainder))
+        }
+        SOCKET_ADDR_V6_TAG => {
+            if remainder.len() < 18 {
+                return Err(bytesrepr::Error::EarlyEndOfStream);
+            }
+            let (octets, remainder) = remainder.split_at(16);
+            let (port_bytes, remainder) = remainder.split_at(2);
+            let mut segments = [0u8; 16];
+            segments.copy_from_slice(octets);
+            let ip = Ipv6Addr::from(segments);
+            let port = u16::from_be_bytes([port_bytes[0], port_bytes[1]]);
+            Ok((SocketAddr::new(IpAddr::V6(ip), port), remainder))
+        }
+        _ => Err(bytesrepr::Error::Formatting),
+    }
+}
+
+fn endpoint_to_bytes(endpoint: &Endpoint) -> Vec<u8> {
+    let mut buffer = socket_addr_to_bytes(&endpoint.address);
+    buffer.push(transport_tag(endpoint.transport));
+    buffer
+}
+
+fn endpoint_serialized_length(endpoint: &Endpoint) -> usize {
+    socket_addr_serialized_length(&endpoint.address) + 1
+}
+
+fn endpoint_from_bytes(bytes: &[u8]) -> Result<(Endpoint, &[u8]), bytesrepr::Error> {
+    let (address, remainder) = socket_addr_from_bytes(bytes)?;
+    let (transport_byte, remainder) = u8::from_bytes(remainder)?;
+    let transport = transport_from_tag(transport_byte)?;
+    Ok((Endpoint::new(address, transport), remainder))
+}
+
+/// Serializes the ordered list of endpoints, preference order preserved, as a length-prefixed
+/// sequence.
+fn endpoints_to_bytes(endpoints: &[Endpoint]) -> Vec<u8> {
+    let mut buffer = vec![endpoints.len() as u8];
+    for endpoint in endpoints {
+        buffer.extend(endpoint_to_bytes(endpoint));
+    }
+    buffer
+}
+
+fn endpoints_serialized_length(endpoints: &[Endpoint]) -> usize {
+    U8_SERIALIZED_LENGTH
+        + endpoints
+            .iter()
+            .map(endpoint_serialized_length)
+            .sum::<usize>()
+}
+
+/// Deserializes the ordered list of endpoints, rejecting counts above `MAX_GOSSIPED_ENDPOINTS`
+/// before any per-endpoint allocation happens, so a hostile peer can't use an inflated count to
+/// force excessive work.
+fn endpoints_from_bytes(bytes: &[u8]) -> Result<(Vec<Endpoint>, &[u8]), bytesrepr::Error> {
+    let (count, mut remainder) = u8::from_bytes(bytes)?;
+    if count == 0 || count as usize > MAX_GOSSIPED_ENDPOINTS {
+        return Err(bytesrepr::Error::Formatting);
+    }
+    let mut endpoints = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (endpoint, next_remainder) = endpoint_from_bytes(remainder)?;
+        endpoints.push(endpoint);
+        remainder = next_remainder;
+    }
+    Ok((endpoints, remainder))
 }
 
-impl From<GossipedAddress> for SocketAddr {
-    fn from(gossiped_address: GossipedAddress) -> Self {
-        gossiped_address.0
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoints() -> Vec<Endpoint> {
+        vec![
+            Endpoint::new("127.0.0.1:34553".parse().unwrap(), Transport::Tcp),
+            Endpoint::new("[::1]:34553".parse().unwrap(), Transport::Quic),
+        ]
+    }
+
+    fn new_gossiped_address(endpoints: Vec<Endpoint>, timestamp: Timestamp) -> GossipedAddress {
+        let secret_key = SecretKey::generate_ed25519().unwrap();
+        GossipedAddress::new(endpoints, timestamp, &secret_key).unwrap()
+    }
+
+    #[test]
+    fn should_validate_freshly_signed_address() {
+        let gossiped_address = new_gossiped_address(endpoints(), Timestamp::now());
+        assert_eq!(
+            gossiped_address.validate_with_freshness_window(DEFAULT_ADDRESS_FRESHNESS_WINDOW),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_signature() {
+        let mut gossiped_address = new_gossiped_address(endpoints(), Timestamp::now());
+        let other_secret_key = SecretKey::generate_ed25519().unwrap();
+        gossiped_address.signature = crypto::sign(
+            GossipedAddress::signing_payload(
+                &gossiped_address.endpoints,
+                gossiped_address.timestamp,
+            ),
+            &other_secret_key,
+            &PublicKey::from(&other_secret_key),
+        );
+        assert_eq!(
+            gossiped_address.validate_with_freshness_window(DEFAULT_ADDRESS_FRESHNESS_WINDOW),
+            Err(GossipedAddressValidationError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn should_reject_stale_timestamp() {
+        let stale_timestamp = Timestamp::now() - TimeDiff::from_seconds(60 * 60);
+        let gossiped_address = new_gossiped_address(endpoints(), stale_timestamp);
+        assert_eq!(
+            gossiped_address.validate_with_freshness_window(DEFAULT_ADDRESS_FRESHNESS_WINDOW),
+            Err(GossipedAddressValidationError::StaleTimestamp)
+        );
+    }
+
+    #[test]
+    fn should_honour_custom_freshness_window() {
+        let stale_timestamp = Timestamp::now() - TimeDiff::from_seconds(60 * 60);
+        let gossiped_address = new_gossiped_address(endpoints(), stale_timestamp);
+
+        // Rejected under the default window...
+        assert_eq!(
+            gossiped_address.validate_with_freshness_window(DEFAULT_ADDRESS_FRESHNESS_WINDOW),
+            Err(GossipedAddressValidationError::StaleTimestamp)
+        );
+        // ...but accepted once the caller supplies a wider, configured window.
+        assert_eq!(
+            gossiped_address.validate_with_freshness_window(TimeDiff::from_seconds(2 * 60 * 60)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn should_reject_construction_with_no_endpoints() {
+        let secret_key = SecretKey::generate_ed25519().unwrap();
+        assert_eq!(
+            GossipedAddress::new(vec![], Timestamp::now(), &secret_key),
+            Err(GossipedAddressConstructionError::NoEndpoints)
+        );
+    }
+
+    #[test]
+    fn should_reject_construction_with_too_many_endpoints() {
+        let secret_key = SecretKey::generate_ed25519().unwrap();
+        let too_many_endpoints: Vec<Endpoint> = (0..MAX_GOSSIPED_ENDPOINTS + 1)
+            .map(|port| {
+                let address: SocketAddr = format!("127.0.0.1:{}", 20000 + port).parse().unwrap();
+                Endpoint::new(address, Transport::Tcp)
+            })
+            .collect();
+        assert_eq!(
+            GossipedAddress::new(too_many_endpoints, Timestamp::now(), &secret_key),
+            Err(GossipedAddressConstructionError::TooManyEndpoints {
+                actual: MAX_GOSSIPED_ENDPOINTS + 1
+            })
+        );
+    }
+
+    #[test]
+    fn endpoints_from_bytes_should_reject_oversized_count() {
+        let oversized_count = (MAX_GOSSIPED_ENDPOINTS + 1) as u8;
+        assert_eq!(
+            endpoints_from_bytes(&[oversized_count]),
+            Err(bytesrepr::Error::Formatting)
+        );
+    }
+
+    #[test]
+    fn bytesrepr_round_trip_should_succeed() {
+        let gossiped_address = new_gossiped_address(endpoints(), Timestamp::now());
+        bytesrepr::test_serialization_roundtrip(&gossiped_address);
+    }
+
+    #[test]
+    fn id_should_include_public_key_of_signer() {
+        let timestamp = Timestamp::now();
+        let first = new_gossiped_address(endpoints(), timestamp);
+        let second = new_gossiped_address(endpoints(), timestamp);
+        assert_ne!(first.id(), second.id());
     }
 }
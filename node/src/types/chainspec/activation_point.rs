@@ -4,6 +4,7 @@
 use std::{
     convert::TryFrom,
     fmt::{self, Display, Formatter},
+    str::FromStr,
 };
 
 use datasize::DataSize;
@@ -12,7 +13,8 @@ use num_traits::cast::{FromPrimitive, ToPrimitive};
 #[cfg(test)]
 use rand::Rng;
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use thiserror::Error;
 
 use casper_types::{
     bytesrepr::{self, FromBytes, ToBytes, U8_SERIALIZED_LENGTH},
@@ -27,6 +29,7 @@ use crate::types::Timestamp;
 enum ActivationPointTag {
     EraId = 0,
     Genesis = 1,
+    BlockHeight = 2,
 }
 
 impl TryFrom<u8> for ActivationPointTag {
@@ -38,11 +41,121 @@ impl TryFrom<u8> for ActivationPointTag {
 }
 
 /// The first era to which the associated protocol version applies.
-#[derive(Copy, Clone, DataSize, PartialEq, Eq, Serialize, Deserialize, Debug, JsonSchema)]
-#[serde(untagged)]
+#[derive(Copy, Clone, DataSize, PartialEq, Eq, Debug, JsonSchema)]
 pub enum ActivationPoint {
     EraId(EraId),
     Genesis(Timestamp),
+    BlockHeight(u64),
+}
+
+/// The error type returned when parsing an [`ActivationPoint`] from its compact string form
+/// fails.
+#[derive(Clone, Eq, PartialEq, Debug, Error)]
+pub enum ActivationPointParseError {
+    /// The string didn't start with a recognised `era:`, `genesis:` or `height:` prefix.
+    #[error(
+        "activation point must be prefixed with \"era:\", \"genesis:\" or \"height:\", got \
+         \"{0}\""
+    )]
+    UnknownPrefix(String),
+    /// The era ID following the `era:` prefix wasn't a valid number.
+    #[error("invalid era ID in activation point \"{0}\"")]
+    InvalidEraId(String),
+    /// The timestamp following the `genesis:` prefix wasn't a valid RFC 3339 timestamp.
+    #[error("invalid genesis timestamp in activation point \"{0}\"")]
+    InvalidTimestamp(String),
+    /// The height following the `height:` prefix wasn't a valid number.
+    #[error("invalid block height in activation point \"{0}\"")]
+    InvalidBlockHeight(String),
+}
+
+impl FromStr for ActivationPoint {
+    type Err = ActivationPointParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some(era_id) = value.strip_prefix("era:") {
+            let era_id: u64 = era_id
+                .parse()
+                .map_err(|_| ActivationPointParseError::InvalidEraId(value.to_string()))?;
+            return Ok(ActivationPoint::EraId(EraId::from(era_id)));
+        }
+
+        if let Some(timestamp) = value.strip_prefix("genesis:") {
+            let timestamp = timestamp
+                .parse::<Timestamp>()
+                .map_err(|_| ActivationPointParseError::InvalidTimestamp(value.to_string()))?;
+            return Ok(ActivationPoint::Genesis(timestamp));
+        }
+
+        if let Some(height) = value.strip_prefix("height:") {
+            let height: u64 = height
+                .parse()
+                .map_err(|_| ActivationPointParseError::InvalidBlockHeight(value.to_string()))?;
+            return Ok(ActivationPoint::BlockHeight(height));
+        }
+
+        Err(ActivationPointParseError::UnknownPrefix(value.to_string()))
+    }
+}
+
+/// The untagged wire representation used by already-launched chainspecs: a bare integer for an
+/// era boundary, or a bare RFC 3339 timestamp string for genesis.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ActivationPointWireFormat {
+    EraId(EraId),
+    Genesis(Timestamp),
+}
+
+impl Serialize for ActivationPoint {
+    /// Serializes in the original untagged wire format for `EraId`/`Genesis`, preserving
+    /// compatibility with already-launched networks' chainspecs.  `BlockHeight`, which has no
+    /// legacy on-wire form, serializes as its compact `"height:<N>"` string.
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            ActivationPoint::EraId(era_id) => {
+                ActivationPointWireFormat::EraId(*era_id).serialize(serializer)
+            }
+            ActivationPoint::Genesis(timestamp) => {
+                ActivationPointWireFormat::Genesis(*timestamp).serialize(serializer)
+            }
+            ActivationPoint::BlockHeight(_) => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ActivationPoint {
+    /// Accepts the original untagged wire format (a bare integer or bare RFC 3339 timestamp
+    /// string) to stay compatible with already-launched chainspecs, as well as the compact
+    /// `"era:<N>"`/`"genesis:<ts>"`/`"height:<N>"` string form.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(u64),
+            Str(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(era_id) => Ok(ActivationPoint::EraId(EraId::from(era_id))),
+            Repr::Str(value) => {
+                if let Ok(activation_point) = value.parse() {
+                    return Ok(activation_point);
+                }
+                // Fall back to the original bare-timestamp genesis format.
+                let timestamp = value.parse::<Timestamp>().map_err(|_| {
+                    de::Error::custom(format!("invalid activation point \"{}\"", value))
+                })?;
+                Ok(ActivationPoint::Genesis(timestamp))
+            }
+        }
+    }
 }
 
 impl ActivationPoint {
@@ -50,6 +163,7 @@ impl ActivationPoint {
         match self {
             ActivationPoint::EraId(_) => ActivationPointTag::EraId,
             ActivationPoint::Genesis(_) => ActivationPointTag::Genesis,
+            ActivationPoint::BlockHeight(_) => ActivationPointTag::BlockHeight,
         }
     }
 
@@ -63,22 +177,31 @@ impl ActivationPoint {
     pub(crate) fn should_upgrade(&self, era_being_deactivated: &EraId) -> bool {
         match self {
             ActivationPoint::EraId(era_id) => era_being_deactivated.successor() >= *era_id,
-            ActivationPoint::Genesis(_) => false,
+            ActivationPoint::Genesis(_) | ActivationPoint::BlockHeight(_) => false,
+        }
+    }
+
+    /// Returns whether we should upgrade the node due to the given block height being at or
+    /// after this upgrade's activation point.
+    pub(crate) fn should_upgrade_at_height(&self, current_height: u64) -> bool {
+        match self {
+            ActivationPoint::BlockHeight(height) => current_height >= *height,
+            ActivationPoint::EraId(_) | ActivationPoint::Genesis(_) => false,
         }
     }
 
-    /// Returns the Era ID if `self` is of `EraId` variant, or else 0 if `Genesis`.
+    /// Returns the Era ID if `self` is of `EraId` variant, or else 0 otherwise.
     pub(crate) fn era_id(&self) -> EraId {
         match self {
             ActivationPoint::EraId(era_id) => *era_id,
-            ActivationPoint::Genesis(_) => EraId::from(0),
+            ActivationPoint::Genesis(_) | ActivationPoint::BlockHeight(_) => EraId::from(0),
         }
     }
 
     /// Returns the timestamp if `self` is of `Genesis` variant, or else `None`.
     pub(crate) fn genesis_timestamp(&self) -> Option<Timestamp> {
         match self {
-            ActivationPoint::EraId(_) => None,
+            ActivationPoint::EraId(_) | ActivationPoint::BlockHeight(_) => None,
             ActivationPoint::Genesis(timestamp) => Some(*timestamp),
         }
     }
@@ -86,7 +209,7 @@ impl ActivationPoint {
     /// Returns true if `self` is `Genesis`.
     pub(crate) fn is_genesis(&self) -> bool {
         match self {
-            ActivationPoint::EraId(_) => false,
+            ActivationPoint::EraId(_) | ActivationPoint::BlockHeight(_) => false,
             ActivationPoint::Genesis(_) => true,
         }
     }
@@ -95,10 +218,9 @@ impl ActivationPoint {
 impl Display for ActivationPoint {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            ActivationPoint::EraId(era_id) => write!(formatter, "activation point {}", era_id),
-            ActivationPoint::Genesis(timestamp) => {
-                write!(formatter, "activation point {}", timestamp)
-            }
+            ActivationPoint::EraId(era_id) => write!(formatter, "era:{}", era_id),
+            ActivationPoint::Genesis(timestamp) => write!(formatter, "genesis:{}", timestamp),
+            ActivationPoint::BlockHeight(height) => write!(formatter, "height:{}", height),
         }
     }
 }
@@ -115,6 +237,10 @@ impl ToBytes for ActivationPoint {
                 buffer.extend(timestamp.to_bytes()?);
                 Ok(buffer)
             }
+            ActivationPoint::BlockHeight(height) => {
+                buffer.extend(height.to_bytes()?);
+                Ok(buffer)
+            }
         }
     }
 
@@ -123,6 +249,7 @@ impl ToBytes for ActivationPoint {
             + match self {
                 ActivationPoint::EraId(era_id) => era_id.serialized_length(),
                 ActivationPoint::Genesis(timestamp) => timestamp.serialized_length(),
+                ActivationPoint::BlockHeight(height) => height.serialized_length(),
             }
     }
 }
@@ -139,6 +266,10 @@ impl FromBytes for ActivationPoint {
                 let (timestamp, remainder) = Timestamp::from_bytes(remainder)?;
                 Ok((ActivationPoint::Genesis(timestamp), remainder))
             }
+            ActivationPointTag::BlockHeight => {
+                let (height, remainder) = u64::from_bytes(remainder)?;
+                Ok((ActivationPoint::BlockHeight(height), remainder))
+            }
         }
     }
 }
@@ -147,10 +278,155 @@ impl FromBytes for ActivationPoint {
 impl ActivationPoint {
     /// Generates a random instance using a `TestRng`.
     pub fn random(rng: &mut TestRng) -> Self {
-        if rng.gen() {
-            ActivationPoint::EraId(rng.gen())
-        } else {
-            ActivationPoint::Genesis(Timestamp::random(rng))
+        match rng.gen_range(0..3) {
+            0 => ActivationPoint::EraId(rng.gen()),
+            1 => ActivationPoint::Genesis(Timestamp::random(rng)),
+            _ => ActivationPoint::BlockHeight(rng.gen()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_era_id() {
+        assert_eq!(
+            "era:5".parse::<ActivationPoint>().unwrap(),
+            ActivationPoint::EraId(EraId::from(5))
+        );
+    }
+
+    #[test]
+    fn should_parse_block_height() {
+        assert_eq!(
+            "height:42".parse::<ActivationPoint>().unwrap(),
+            ActivationPoint::BlockHeight(42)
+        );
+    }
+
+    #[test]
+    fn should_reject_unknown_prefix() {
+        assert_eq!(
+            "banana:1".parse::<ActivationPoint>(),
+            Err(ActivationPointParseError::UnknownPrefix(
+                "banana:1".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn should_reject_non_numeric_era_id() {
+        assert_eq!(
+            "era:not-a-number".parse::<ActivationPoint>(),
+            Err(ActivationPointParseError::InvalidEraId(
+                "era:not-a-number".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn should_reject_non_numeric_block_height() {
+        assert_eq!(
+            "height:not-a-number".parse::<ActivationPoint>(),
+            Err(ActivationPointParseError::InvalidBlockHeight(
+                "height:not-a-number".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn should_reject_unparseable_genesis_timestamp() {
+        assert_eq!(
+            "genesis:not-a-timestamp".parse::<ActivationPoint>(),
+            Err(ActivationPointParseError::InvalidTimestamp(
+                "genesis:not-a-timestamp".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn display_and_from_str_should_round_trip() {
+        let mut rng = TestRng::new();
+        for _ in 0..10 {
+            let activation_point = ActivationPoint::random(&mut rng);
+            let round_tripped: ActivationPoint = activation_point.to_string().parse().unwrap();
+            assert_eq!(activation_point, round_tripped);
+        }
+    }
+
+    #[test]
+    fn should_deserialize_legacy_untagged_era_id() {
+        assert_eq!(
+            serde_json::from_str::<ActivationPoint>("5").unwrap(),
+            ActivationPoint::EraId(EraId::from(5))
+        );
+    }
+
+    #[test]
+    fn should_deserialize_legacy_untagged_genesis_timestamp() {
+        let timestamp = Timestamp::from_str("2020-01-01T00:00:00.000Z").unwrap();
+        let legacy_payload = format!("\"{}\"", timestamp);
+        assert_eq!(
+            serde_json::from_str::<ActivationPoint>(&legacy_payload).unwrap(),
+            ActivationPoint::Genesis(timestamp)
+        );
+    }
+
+    #[test]
+    fn should_serialize_era_id_and_genesis_in_legacy_untagged_format() {
+        let era_id = ActivationPoint::EraId(EraId::from(5));
+        assert_eq!(serde_json::to_string(&era_id).unwrap(), "5");
+
+        let timestamp = Timestamp::from_str("2020-01-01T00:00:00.000Z").unwrap();
+        let genesis = ActivationPoint::Genesis(timestamp);
+        assert_eq!(
+            serde_json::to_string(&genesis).unwrap(),
+            format!("\"{}\"", timestamp)
+        );
+    }
+
+    #[test]
+    fn should_deserialize_compact_string_form() {
+        assert_eq!(
+            serde_json::from_str::<ActivationPoint>("\"era:5\"").unwrap(),
+            ActivationPoint::EraId(EraId::from(5))
+        );
+        assert_eq!(
+            serde_json::from_str::<ActivationPoint>("\"height:42\"").unwrap(),
+            ActivationPoint::BlockHeight(42)
+        );
+    }
+
+    #[test]
+    fn serde_round_trip_should_succeed() {
+        let mut rng = TestRng::new();
+        for _ in 0..10 {
+            let activation_point = ActivationPoint::random(&mut rng);
+            let serialized = serde_json::to_string(&activation_point).unwrap();
+            let deserialized: ActivationPoint = serde_json::from_str(&serialized).unwrap();
+            assert_eq!(activation_point, deserialized);
+        }
+    }
+
+    #[test]
+    fn bytesrepr_round_trip_should_succeed() {
+        let mut rng = TestRng::new();
+        for _ in 0..10 {
+            let activation_point = ActivationPoint::random(&mut rng);
+            bytesrepr::test_serialization_roundtrip(&activation_point);
+        }
+    }
+
+    #[test]
+    fn should_upgrade_at_height_should_only_trigger_for_block_height_variant() {
+        let activation_point = ActivationPoint::BlockHeight(100);
+        assert!(!activation_point.should_upgrade_at_height(99));
+        assert!(activation_point.should_upgrade_at_height(100));
+        assert!(activation_point.should_upgrade_at_height(101));
+
+        let era_activation_point = ActivationPoint::EraId(EraId::from(5));
+        assert!(!era_activation_point.should_upgrade_at_height(1_000_000));
+    }
+}